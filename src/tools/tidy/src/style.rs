@@ -11,13 +11,243 @@
 //!
 //! A number of these checks can be opted-out of with various directives of the form:
 //! `// ignore-tidy-CHECK-NAME`.
+//!
+//! A single line can instead be opted-out of one check with `// tidy-ignore-line CHECK-NAME`,
+//! or have the following line opted-out with `// tidy-ignore-next-line CHECK-NAME`.
+//! Only the `linelength`, `tab`, `end-whitespace` and `cr` checks honor these markers; a
+//! marker naming any other check suppresses nothing and is reported as unnecessary.
+//!
+//! The column limit, checked extensions, individual check toggles and extra allowed overlength
+//! patterns can all be overridden by a `tidy.toml` file, searched for from the checked path
+//! upward; see `Config` for the supported keys.
+
+// Requires a `[dependencies]` entry for `rayon` in this crate's Cargo.toml.
+extern crate rayon;
+// Requires a `[dependencies]` entry for `toml` in this crate's Cargo.toml.
+extern crate toml;
 
+use self::rayon::prelude::*;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::fs::File;
 use std::io::prelude::*;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+
+/// Whether a `check` run should only report violations or also rewrite
+/// fixable ones in place.
+#[derive(Copy, Clone, PartialEq)]
+pub enum Mode {
+    /// Only report violations; this is the historical behavior.
+    Check,
+    /// Rewrite mechanically-fixable violations in place, still reporting
+    /// anything that cannot be fixed automatically.
+    Fix,
+}
+
+/// How violations are reported.
+#[derive(Copy, Clone, PartialEq)]
+pub enum Format {
+    /// Free-form `file:line: message` text; the historical format.
+    Text,
+    /// One JSON object per violation, for editor/CI integration. Each object
+    /// has `file`, `line`, `column`, `check`, `message` and `fixable` fields.
+    Json,
+}
+
+/// Escapes `s` as a JSON string literal, including the surrounding quotes.
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Formats a single diagnostic as free-form text or as a JSON record,
+/// per `format`. Shared by the per-line/per-file checks and by `Config::load`,
+/// so a malformed `tidy.toml` is reported through the same channel as every
+/// other violation instead of bypassing `format`.
+fn format_message(
+    file: &Path,
+    line: Option<usize>,
+    check: &str,
+    fixable: bool,
+    msg: &str,
+    format: Format,
+) -> String {
+    match format {
+        Format::Text => match line {
+            Some(line) => format!("tidy error: {}:{}: {}", file.display(), line + 1, msg),
+            None => format!("tidy error: {}: {}", file.display(), msg),
+        },
+        Format::Json => format!(
+            "{{\"file\":{},\"line\":{},\"column\":null,\"check\":{},\
+             \"message\":{},\"fixable\":{}}}",
+            json_string(&file.display().to_string()),
+            line.map(|l| (l + 1).to_string()).unwrap_or_else(|| "null".to_string()),
+            json_string(check),
+            json_string(msg),
+            fixable
+        ),
+    }
+}
 
 const COLS: usize = 100;
 
+const DEFAULT_EXTENSIONS: &[&str] = &[".rs", ".py", ".js", ".sh", ".c", ".cpp", ".h"];
+
+const CONFIG_FILE_NAME: &str = "tidy.toml";
+
+const KNOWN_CONFIG_KEYS: &[&str] = &["cols", "extensions", "checks", "allow-overlength"];
+
+/// The check identifiers recognized by the `[checks]` table, matching the
+/// names passed to `config.check_enabled` throughout `check_file`.
+const KNOWN_CHECKS: &[&str] = &[
+    "linelength",
+    "tab",
+    "end-whitespace",
+    "cr",
+    "copyright",
+    "todo",
+    "ignore-doctest",
+    "llvm-unreachable",
+];
+
+/// Configuration loaded from a `tidy.toml`, overriding the built-in
+/// defaults. Any key not present keeps its default behavior.
+#[derive(Default)]
+struct Config {
+    /// Overrides `COLS` when set.
+    cols: Option<usize>,
+    /// Extensions to add to or remove from `DEFAULT_EXTENSIONS`, e.g.
+    /// `".md"` to add or `"-.sh"` to remove.
+    extensions: Option<Vec<String>>,
+    /// Per-check enable/disable toggles, keyed by the same check
+    /// identifiers used elsewhere in this module (`"linelength"`, `"tab"`,
+    /// etc.).
+    checks: HashMap<String, bool>,
+    /// Extra substrings, beyond `line_is_url`, that make an overlength line OK.
+    allow_overlength: Vec<String>,
+}
+
+impl Config {
+    /// Searches `path` and its ancestors for a `tidy.toml`, parsing it if
+    /// found. Unknown keys and an invalid file are reported through `bad`,
+    /// formatted per `format` like every other diagnostic; the absence of a
+    /// config file is not an error.
+    fn load(path: &Path, bad: &mut bool, format: Format) -> Config {
+        let config_path = match Config::find(path) {
+            Some(p) => p,
+            None => return Config::default(),
+        };
+
+        let mut config_error = |msg: &str| {
+            *bad = true;
+            println!("{}", format_message(&config_path, None, "config", false, msg, format));
+        };
+
+        let mut contents = String::new();
+        t!(t!(File::open(&config_path), config_path).read_to_string(&mut contents));
+
+        let value: toml::Value = match contents.parse() {
+            Ok(value) => value,
+            Err(e) => {
+                config_error(&format!("could not parse tidy.toml: {}", e));
+                return Config::default();
+            }
+        };
+        let table = match value.as_table() {
+            Some(table) => table,
+            None => {
+                config_error("tidy.toml must be a table");
+                return Config::default();
+            }
+        };
+
+        for key in table.keys() {
+            if !KNOWN_CONFIG_KEYS.contains(&key.as_str()) {
+                config_error(&format!("unknown tidy.toml key `{}`", key));
+            }
+        }
+
+        let mut checks = HashMap::new();
+        if let Some(table_checks) = table.get("checks").and_then(|v| v.as_table()) {
+            for (check, enabled) in table_checks {
+                if !KNOWN_CHECKS.contains(&check.as_str()) {
+                    config_error(&format!("unknown tidy.toml check `{}`", check));
+                    continue;
+                }
+                if let Some(enabled) = enabled.as_bool() {
+                    checks.insert(check.clone(), enabled);
+                }
+            }
+        }
+
+        Config {
+            cols: table.get("cols").and_then(|v| v.as_integer()).map(|n| n as usize),
+            extensions: table.get("extensions").and_then(|v| v.as_array()).map(|a| {
+                a.iter().filter_map(|v| v.as_str().map(str::to_string)).collect()
+            }),
+            checks,
+            allow_overlength: table.get("allow-overlength").and_then(|v| v.as_array()).map_or_else(
+                Vec::new,
+                |patterns| patterns.iter().filter_map(|v| v.as_str().map(str::to_string)).collect(),
+            ),
+        }
+    }
+
+    /// Searches upward from `path` (inclusive) for a `tidy.toml` file.
+    fn find(path: &Path) -> Option<PathBuf> {
+        let mut dir = if path.is_dir() { Some(path) } else { path.parent() };
+        while let Some(d) = dir {
+            let candidate = d.join(CONFIG_FILE_NAME);
+            if candidate.is_file() {
+                return Some(candidate);
+            }
+            dir = d.parent();
+        }
+        None
+    }
+
+    /// Whether `check` should run at all, per the `[checks]` table.
+    /// Defaults to enabled.
+    fn check_enabled(&self, check: &str) -> bool {
+        *self.checks.get(check).unwrap_or(&true)
+    }
+
+    /// The effective line-length limit, honoring `cols` if set.
+    fn cols(&self) -> usize {
+        self.cols.unwrap_or(COLS)
+    }
+
+    /// The effective set of checked extensions, applying `extensions`
+    /// overrides (entries prefixed with `-` remove, everything else adds)
+    /// on top of `DEFAULT_EXTENSIONS`.
+    fn extensions(&self) -> Vec<String> {
+        let mut extensions: Vec<String> =
+            DEFAULT_EXTENSIONS.iter().map(|s| (*s).to_string()).collect();
+        if let Some(overrides) = &self.extensions {
+            for entry in overrides {
+                if let Some(removed) = entry.strip_prefix('-') {
+                    extensions.retain(|e| e != removed);
+                } else if !extensions.iter().any(|e| e == entry) {
+                    extensions.push(entry.clone());
+                }
+            }
+        }
+        extensions
+    }
+}
+
 const UNEXPLAINED_IGNORE_DOCTEST_INFO: &str = r#"unexplained "```ignore" doctest; try one:
 
 * make the test actually pass, by adding necessary imports and declarations, or
@@ -80,14 +310,14 @@ fn line_is_url(line: &str) -> bool {
 }
 
 /// Returns `true` if `line` is allowed to be longer than the normal limit.
-/// Currently there is only one exception, for long URLs, but more
-/// may be added in the future.
-fn long_line_is_ok(line: &str) -> bool {
+/// Long URLs are always allowed; `extra_patterns` adds further substrings
+/// (from `tidy.toml`'s `allow-overlength`) that also make a line OK.
+fn long_line_is_ok(line: &str, extra_patterns: &[String]) -> bool {
     if line_is_url(line) {
         return true;
     }
 
-    false
+    extra_patterns.iter().any(|pattern| line.contains(pattern.as_str()))
 }
 
 enum Directive {
@@ -110,10 +340,117 @@ fn contains_ignore_directive(contents: &String, check: &str) -> Directive {
     }
 }
 
+/// A line-scoped suppression for `check`, introduced by a
+/// `// tidy-ignore-line` or `// tidy-ignore-next-line` marker on
+/// `directive_line` (0-indexed).
+struct LineSuppression {
+    check: String,
+    directive_line: usize,
+}
+
+/// Returns the text of `line`'s line comment (everything after the
+/// earliest `//` or `#`), or `None` if `line` carries no such comment.
+/// Used to anchor suppression markers to an actual comment instead of
+/// matching anywhere in the line, e.g. inside a string literal or prose
+/// that merely mentions the marker syntax.
+fn comment_text(line: &str) -> Option<&str> {
+    let slash = line.find("//").map(|i| (i, 2));
+    let hash = line.find('#').map(|i| (i, 1));
+    let (idx, skip) = match (slash, hash) {
+        (Some(a), Some(b)) => if a.0 <= b.0 { a } else { b },
+        (Some(a), None) => a,
+        (None, Some(b)) => b,
+        (None, None) => return None,
+    };
+    Some(&line[idx + skip..])
+}
+
+/// Returns `true` if `s` is a single check-identifier-like token (as used
+/// elsewhere in this module, e.g. `"linelength"`, `"end-whitespace"`):
+/// non-empty and made up only of ASCII alphanumerics, `-` and `_`.
+fn is_check_identifier(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// If `line` carries a trailing `// tidy-ignore-line CHECK` or
+/// `// tidy-ignore-next-line CHECK` marker, returns the suppressed check name
+/// and whether the marker applies to the next line rather than this one.
+/// The marker must be the tail of a genuine line comment, with the check
+/// name bound to a single identifier-like token, so that a doc comment or
+/// string literal merely mentioning the marker syntax is not mistaken for
+/// one.
+fn line_suppression_marker(line: &str) -> Option<(&str, bool)> {
+    let line = line.trim_end();
+    let comment = comment_text(line)?.trim();
+    let tokens: Vec<&str> = comment.split_whitespace().collect();
+    let (marker, check) = match tokens.as_slice() {
+        [.., marker, check] => (*marker, *check),
+        _ => return None,
+    };
+    if !is_check_identifier(check) {
+        return None;
+    }
+    match marker {
+        "tidy-ignore-next-line" => Some((check, true)),
+        "tidy-ignore-line" => Some((check, false)),
+        _ => None,
+    }
+}
+
+/// Collects the line-scoped suppressions in `contents`, keyed by the
+/// (0-indexed) line they apply to. Each line holds at most one suppression:
+/// a later marker targeting the same line replaces an earlier one.
+fn collect_line_suppressions(contents: &str) -> BTreeMap<usize, LineSuppression> {
+    let mut suppressions = BTreeMap::new();
+    for (i, line) in contents.split('\n').enumerate() {
+        if let Some((check, next_line)) = line_suppression_marker(line) {
+            let target = if next_line { i + 1 } else { i };
+            let suppression = LineSuppression { check: check.to_string(), directive_line: i };
+            suppressions.insert(target, suppression);
+        }
+    }
+    suppressions
+}
+
+/// Returns whether `line_no` is suppressed for `check` by an inline
+/// suppression, recording the suppression as used if so.
+fn take_line_suppression(
+    line_no: usize,
+    check: &str,
+    suppressions: &BTreeMap<usize, LineSuppression>,
+    used: &mut BTreeSet<usize>,
+) -> bool {
+    match suppressions.get(&line_no) {
+        Some(s) if s.check == check => {
+            used.insert(line_no);
+            true
+        }
+        _ => false,
+    }
+}
+
 macro_rules! suppressible_tidy_err {
-    ($err:ident, $skip:ident, $msg:expr) => {
+    ($err:ident, $skip:ident, $check:expr, $msg:expr) => {
         if let Directive::Deny = $skip {
-            $err($msg);
+            $err($check, false, $msg);
+        } else {
+            $skip = Directive::Ignore(true);
+        }
+    };
+}
+
+/// Like `suppressible_tidy_err!`, but for violations that `Mode::Fix` knows
+/// how to repair. When fixing and no `ignore-tidy-*` directive applies, the
+/// `$fix` block runs instead of reporting an error. A violation covered by
+/// the relevant directive is never fixed, in either mode.
+macro_rules! fixable_tidy_err {
+    ($err:ident, $skip:ident, $check:expr, $msg:expr, $mode:expr, $fix:block) => {
+        if let Directive::Deny = $skip {
+            if $mode == Mode::Fix {
+                $fix
+            } else {
+                $err($check, true, $msg);
+            }
         } else {
             $skip = Directive::Ignore(true);
         }
@@ -121,106 +458,400 @@ macro_rules! suppressible_tidy_err {
 }
 
 pub fn check(path: &Path, bad: &mut bool) {
-    let mut contents = String::new();
+    check_with_mode(path, bad, Mode::Check, Format::Text)
+}
+
+/// The outcome of running all checks against a single file: whether it was
+/// bad, and its already-formatted messages (text or JSON, per `Format`) in
+/// the order they were produced.
+struct FileReport {
+    path: PathBuf,
+    bad: bool,
+    messages: Vec<String>,
+}
+
+/// Runs the style checks rooted at `path`, reporting violations through
+/// `bad`. When `mode` is `Mode::Fix`, mechanically-fixable violations
+/// (trailing whitespace, tabs, CR characters, leading blank lines, and
+/// trailing newline count) are rewritten in place instead of reported;
+/// everything else is always reported. A file is only rewritten if its
+/// contents actually change, and violations covered by an `ignore-tidy-*`
+/// directive in the file are left as they are. `format` controls whether
+/// violations are printed as free-form text or as JSON records.
+pub fn check_with_mode(path: &Path, bad: &mut bool, mode: Mode, format: Format) {
+    let config = Config::load(path, bad, format);
+    let extensions = config.extensions();
+
+    let mut files = Vec::new();
     super::walk(path, &mut super::filter_dirs, &mut |file| {
         let filename = file.file_name().unwrap().to_string_lossy();
-        let extensions = [".rs", ".py", ".js", ".sh", ".c", ".cpp", ".h"];
-        if extensions.iter().all(|e| !filename.ends_with(e)) ||
+        if extensions.iter().all(|e| !filename.ends_with(e.as_str())) ||
            filename.starts_with(".#") {
             return
         }
+        files.push(file.to_path_buf());
+    });
 
-        contents.truncate(0);
-        t!(t!(File::open(file), file).read_to_string(&mut contents));
+    // Each file is fully independent, so the per-file line analysis runs
+    // across a thread pool; only the ordering of the final report needs to
+    // be stabilized for reproducible output.
+    let mut reports: Vec<FileReport> = files.par_iter()
+        .map(|file| check_file(file, mode, format, &config))
+        .collect();
+    reports.sort_by(|a, b| a.path.cmp(&b.path));
 
-        if contents.is_empty() {
-            tidy_error!(bad, "{}: empty file", file.display());
+    for report in reports {
+        for message in &report.messages {
+            println!("{}", message);
+        }
+        if report.bad {
+            *bad = true;
         }
+    }
+}
 
-        let mut skip_cr = contains_ignore_directive(&contents, "cr");
-        let mut skip_tab = contains_ignore_directive(&contents, "tab");
-        let mut skip_length = contains_ignore_directive(&contents, "linelength");
-        let mut skip_end_whitespace = contains_ignore_directive(&contents, "end-whitespace");
-        let mut skip_copyright = contains_ignore_directive(&contents, "copyright");
-        let mut leading_new_lines = false;
-        let mut trailing_new_lines = 0;
-        for (i, line) in contents.split('\n').enumerate() {
-            let mut err = |msg: &str| {
-                tidy_error!(bad, "{}:{}: {}", file.display(), i + 1, msg);
-            };
-            if line.chars().count() > COLS && !long_line_is_ok(line) {
+/// Applies `Mode::Fix`'s whole-file normalization to an already
+/// line-fixed file: drops leading blank lines (short of dropping the only
+/// line there is), then drops all trailing blank lines, then appends
+/// exactly one trailing newline. An all-blank file normalizes to empty.
+fn normalize_fixed_lines(mut lines: Vec<String>) -> String {
+    while lines.first().is_some_and(|l| l.is_empty()) && lines.len() > 1 {
+        lines.remove(0);
+    }
+    while lines.last().is_some_and(|l| l.is_empty()) {
+        lines.pop();
+    }
+    lines.push(String::new());
+    lines.join("\n")
+}
+
+/// Runs all style checks against a single `file`, returning its report
+/// rather than printing or mutating shared state directly, so callers can
+/// run this across many files in parallel.
+fn check_file(file: &Path, mode: Mode, format: Format, config: &Config) -> FileReport {
+    let cols = config.cols();
+
+    let mut contents = String::new();
+    t!(t!(File::open(file), file).read_to_string(&mut contents));
+
+    let mut bad = false;
+    let mut messages: Vec<String> = Vec::new();
+    let mut report = |line: Option<usize>, check: &str, fixable: bool, msg: &str| {
+        bad = true;
+        messages.push(format_message(file, line, check, fixable, msg, format));
+    };
+
+    if contents.is_empty() {
+        report(None, "empty-file", false, "empty file");
+    }
+
+    let mut skip_cr = contains_ignore_directive(&contents, "cr");
+    let mut skip_tab = contains_ignore_directive(&contents, "tab");
+    let mut skip_length = contains_ignore_directive(&contents, "linelength");
+    let mut skip_end_whitespace = contains_ignore_directive(&contents, "end-whitespace");
+    let mut skip_copyright = contains_ignore_directive(&contents, "copyright");
+    let line_suppressions = collect_line_suppressions(&contents);
+    let mut used_line_suppressions = BTreeSet::new();
+    let mut leading_new_lines = false;
+    let mut trailing_new_lines = 0;
+    let mut fixed_lines = Vec::new();
+    let filename = file.file_name().unwrap().to_string_lossy();
+    for (i, line) in contents.split('\n').enumerate() {
+        let mut err = |check: &str, fixable: bool, msg: &str| report(Some(i), check, fixable, msg);
+        if config.check_enabled("linelength") &&
+           line.chars().count() > cols && !long_line_is_ok(line, &config.allow_overlength) {
+            let suppressed = take_line_suppression(
+                i, "linelength", &line_suppressions, &mut used_line_suppressions
+            );
+            if !suppressed {
                 suppressible_tidy_err!(
                     err,
                     skip_length,
-                    &format!("line longer than {} chars", COLS)
+                    "linelength",
+                    &format!("line longer than {} chars", cols)
                 );
             }
-            if line.contains('\t') {
-                suppressible_tidy_err!(err, skip_tab, "tab character");
-            }
-            if line.ends_with(' ') || line.ends_with('\t') {
-                suppressible_tidy_err!(err, skip_end_whitespace, "trailing whitespace");
-            }
-            if line.contains('\r') {
-                suppressible_tidy_err!(err, skip_cr, "CR character");
+        }
+        let mut fixed_line = line.to_string();
+        if config.check_enabled("tab") && fixed_line.contains('\t') {
+            if take_line_suppression(i, "tab", &line_suppressions, &mut used_line_suppressions) {
+                // explicitly allowed for this line only; leave it untouched
+            } else {
+                fixable_tidy_err!(err, skip_tab, "tab", "tab character", mode, {
+                    fixed_line = fixed_line.replace('\t', "    ");
+                });
             }
-            if filename != "style.rs" {
-                if line.contains("TODO") {
-                    err("TODO is deprecated; use FIXME")
-                }
-                if line.contains("//") && line.contains(" XXX") {
-                    err("XXX is deprecated; use FIXME")
-                }
+        }
+        if config.check_enabled("end-whitespace") &&
+           (fixed_line.ends_with(' ') || fixed_line.ends_with('\t')) {
+            let suppressed = take_line_suppression(
+                i, "end-whitespace", &line_suppressions, &mut used_line_suppressions
+            );
+            if suppressed {
+                // explicitly allowed for this line only; leave it untouched
+            } else {
+                fixable_tidy_err!(
+                    err, skip_end_whitespace, "end-whitespace", "trailing whitespace", mode, {
+                    fixed_line = fixed_line.trim_end().to_string();
+                });
             }
-            if (line.starts_with("// Copyright") ||
-                line.starts_with("# Copyright") ||
-                line.starts_with("Copyright"))
-                && (line.contains("Rust Developers") ||
-                    line.contains("Rust Project Developers")) {
-                suppressible_tidy_err!(
-                    err,
-                    skip_copyright,
-                    "copyright notices attributed to the Rust Project Developers are deprecated"
-                );
+        }
+        if config.check_enabled("cr") && fixed_line.contains('\r') {
+            if take_line_suppression(i, "cr", &line_suppressions, &mut used_line_suppressions) {
+                // explicitly allowed for this line only; leave it untouched
+            } else {
+                fixable_tidy_err!(err, skip_cr, "cr", "CR character", mode, {
+                    fixed_line = fixed_line.replace('\r', "");
+                });
             }
-            if line.ends_with("```ignore") || line.ends_with("```rust,ignore") {
-                err(UNEXPLAINED_IGNORE_DOCTEST_INFO);
+        }
+        if config.check_enabled("todo") && filename != "style.rs" {
+            if line.contains("TODO") {
+                err("todo", false, "TODO is deprecated; use FIXME")
             }
-            if filename.ends_with(".cpp") && line.contains("llvm_unreachable") {
-                err(LLVM_UNREACHABLE_INFO);
+            if line.contains("//") && line.contains(" XXX") {
+                err("todo", false, "XXX is deprecated; use FIXME")
             }
-            if line.is_empty() {
-                if i == 0 {
-                    leading_new_lines = true;
-                }
-                trailing_new_lines += 1;
-            } else {
-                trailing_new_lines = 0;
+        }
+        if config.check_enabled("copyright") &&
+           (line.starts_with("// Copyright") ||
+            line.starts_with("# Copyright") ||
+            line.starts_with("Copyright"))
+            && (line.contains("Rust Developers") ||
+                line.contains("Rust Project Developers")) {
+            suppressible_tidy_err!(
+                err,
+                skip_copyright,
+                "copyright",
+                "copyright notices attributed to the Rust Project Developers are deprecated"
+            );
+        }
+        if config.check_enabled("ignore-doctest") &&
+           (line.ends_with("```ignore") || line.ends_with("```rust,ignore")) {
+            err("ignore-doctest", false, UNEXPLAINED_IGNORE_DOCTEST_INFO);
+        }
+        if config.check_enabled("llvm-unreachable") &&
+           filename.ends_with(".cpp") && line.contains("llvm_unreachable") {
+            err("llvm-unreachable", false, LLVM_UNREACHABLE_INFO);
+        }
+        if line.is_empty() {
+            if i == 0 {
+                leading_new_lines = true;
             }
+            trailing_new_lines += 1;
+        } else {
+            trailing_new_lines = 0;
+        }
+        fixed_lines.push(fixed_line);
+    }
+    if mode == Mode::Fix {
+        let new_contents = normalize_fixed_lines(fixed_lines);
+        if new_contents != contents {
+            t!(t!(File::create(file), file).write_all(new_contents.as_bytes()));
         }
+    } else {
         if leading_new_lines {
-            tidy_error!(bad, "{}: leading newline", file.display());
+            report(None, "leading-newline", true, "leading newline");
         }
         match trailing_new_lines {
-            0 => tidy_error!(bad, "{}: missing trailing newline", file.display()),
+            0 => report(None, "trailing-newline", true, "missing trailing newline"),
             1 => {}
-            n => tidy_error!(bad, "{}: too many trailing newlines ({})", file.display(), n),
+            n => report(
+                None,
+                "trailing-newline",
+                true,
+                &format!("too many trailing newlines ({})", n)
+            ),
         };
+    }
 
+    if config.check_enabled("cr") {
         if let Directive::Ignore(false) = skip_cr {
-            tidy_error!(bad, "{}: ignoring CR characters unnecessarily", file.display());
+            report(None, "cr", false, "ignoring CR characters unnecessarily");
         }
+    }
+    if config.check_enabled("tab") {
         if let Directive::Ignore(false) = skip_tab {
-            tidy_error!(bad, "{}: ignoring tab characters unnecessarily", file.display());
+            report(None, "tab", false, "ignoring tab characters unnecessarily");
         }
+    }
+    if config.check_enabled("linelength") {
         if let Directive::Ignore(false) = skip_length {
-            tidy_error!(bad, "{}: ignoring line length unnecessarily", file.display());
+            report(None, "linelength", false, "ignoring line length unnecessarily");
         }
+    }
+    if config.check_enabled("end-whitespace") {
         if let Directive::Ignore(false) = skip_end_whitespace {
-            tidy_error!(bad, "{}: ignoring trailing whitespace unnecessarily", file.display());
+            report(None, "end-whitespace", false, "ignoring trailing whitespace unnecessarily");
         }
+    }
+    if config.check_enabled("copyright") {
         if let Directive::Ignore(false) = skip_copyright {
-            tidy_error!(bad, "{}: ignoring copyright unnecessarily", file.display());
+            report(None, "copyright", false, "ignoring copyright unnecessarily");
         }
-    })
+    }
+
+    for (target_line, suppression) in &line_suppressions {
+        if !used_line_suppressions.contains(target_line) {
+            report(
+                Some(suppression.directive_line),
+                &suppression.check,
+                false,
+                &format!("ignoring {} unnecessarily via inline suppression", suppression.check)
+            );
+        }
+    }
+
+    FileReport { path: file.to_path_buf(), bad, messages }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(s: &[&str]) -> Vec<String> {
+        s.iter().map(|l| l.to_string()).collect()
+    }
+
+    #[test]
+    fn normalize_fixed_lines_leaves_already_normalized_file_untouched() {
+        assert_eq!(normalize_fixed_lines(lines(&["foo", "bar", ""])), "foo\nbar\n");
+    }
+
+    #[test]
+    fn normalize_fixed_lines_drops_leading_blanks() {
+        assert_eq!(normalize_fixed_lines(lines(&["", "", "foo", ""])), "foo\n");
+    }
+
+    #[test]
+    fn normalize_fixed_lines_drops_trailing_blanks() {
+        assert_eq!(normalize_fixed_lines(lines(&["foo", "", "", ""])), "foo\n");
+    }
+
+    #[test]
+    fn normalize_fixed_lines_adds_missing_trailing_newline() {
+        assert_eq!(normalize_fixed_lines(lines(&["foo"])), "foo\n");
+    }
+
+    #[test]
+    fn normalize_fixed_lines_on_empty_file() {
+        assert_eq!(normalize_fixed_lines(lines(&[""])), "");
+    }
+
+    #[test]
+    fn normalize_fixed_lines_on_all_blank_file() {
+        assert_eq!(normalize_fixed_lines(lines(&["", "", ""])), "");
+    }
+
+    #[test]
+    fn json_string_escapes_quotes_and_backslashes() {
+        assert_eq!(json_string(r#"say "hi"\"#), r#""say \"hi\"\\""#);
+    }
+
+    #[test]
+    fn json_string_escapes_common_control_characters() {
+        assert_eq!(json_string("a\nb\tc\rd"), r#""a\nb\tc\rd""#);
+    }
+
+    #[test]
+    fn json_string_escapes_other_control_characters_as_unicode_escapes() {
+        assert_eq!(json_string("\u{1}"), "\"\\u0001\"");
+    }
+
+    #[test]
+    fn json_string_leaves_plain_text_untouched() {
+        assert_eq!(json_string("plain text"), r#""plain text""#);
+    }
+
+    #[test]
+    fn line_suppression_marker_parses_this_line() {
+        assert_eq!(
+            line_suppression_marker("let x = 1; // tidy-ignore-line linelength"),
+            Some(("linelength", false))
+        );
+    }
+
+    #[test]
+    fn line_suppression_marker_parses_next_line() {
+        assert_eq!(
+            line_suppression_marker("// tidy-ignore-next-line tab"),
+            Some(("tab", true))
+        );
+    }
+
+    #[test]
+    fn line_suppression_marker_trims_trailing_whitespace_off_the_check_name() {
+        assert_eq!(
+            line_suppression_marker("// tidy-ignore-line tab   "),
+            Some(("tab", false))
+        );
+    }
+
+    #[test]
+    fn line_suppression_marker_absent_returns_none() {
+        assert_eq!(line_suppression_marker("let x = 1;"), None);
+    }
+
+    #[test]
+    fn line_suppression_marker_ignores_doc_comment_prose_mentioning_the_syntax() {
+        assert_eq!(
+            line_suppression_marker(
+                "//! one check with `// tidy-ignore-line CHECK-NAME`, or the next"
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn line_suppression_marker_ignores_a_string_literal_mentioning_the_syntax() {
+        assert_eq!(
+            line_suppression_marker(
+                "            line_suppression_marker(\"// tidy-ignore-line linelength\"),"
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn config_extensions_defaults_to_the_built_in_list() {
+        let config = Config::default();
+        assert_eq!(
+            config.extensions(),
+            DEFAULT_EXTENSIONS.iter().map(|s| (*s).to_string()).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn config_extensions_adds_new_entries() {
+        let mut config = Config::default();
+        config.extensions = Some(vec![".md".to_string()]);
+        assert!(config.extensions().iter().any(|e| e == ".md"));
+    }
+
+    #[test]
+    fn config_extensions_removes_entries_prefixed_with_a_dash() {
+        let mut config = Config::default();
+        config.extensions = Some(vec!["-.sh".to_string()]);
+        assert!(!config.extensions().iter().any(|e| e == ".sh"));
+    }
+
+    #[test]
+    fn config_extensions_does_not_duplicate_an_already_present_entry() {
+        let mut config = Config::default();
+        config.extensions = Some(vec![".rs".to_string()]);
+        assert_eq!(config.extensions().iter().filter(|e| *e == ".rs").count(), 1);
+    }
+
+    #[test]
+    fn config_check_enabled_defaults_to_true() {
+        let config = Config::default();
+        assert!(config.check_enabled("linelength"));
+    }
+
+    #[test]
+    fn config_check_enabled_honors_an_explicit_toggle() {
+        let mut config = Config::default();
+        config.checks.insert("linelength".to_string(), false);
+        assert!(!config.check_enabled("linelength"));
+    }
 }